@@ -0,0 +1,145 @@
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::FromRow;
+use std::str::FromStr;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Connects to the SQLite database at `database_url` and runs any pending migrations.
+///
+/// Creates the database file if it doesn't exist yet, so a fresh deployment
+/// doesn't have to pre-create it (or remember to append `?mode=rwc` itself)
+/// before its first start.
+pub async fn connect(database_url: &str) -> anyhow::Result<SqlitePool> {
+    let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+    let pool = SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
+
+#[derive(FromRow)]
+pub struct GuildConfigRow {
+    pub guild_id: i64,
+    pub channel_id: i64,
+    pub message_id: Option<i64>,
+}
+
+pub async fn all_guild_configs(pool: &SqlitePool) -> sqlx::Result<Vec<GuildConfigRow>> {
+    sqlx::query_as::<_, GuildConfigRow>("SELECT guild_id, channel_id, message_id FROM guild_config")
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn set_guild_channel(pool: &SqlitePool, guild_id: i64, channel_id: i64) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO guild_config (guild_id, channel_id) VALUES (?1, ?2)
+         ON CONFLICT(guild_id) DO UPDATE SET channel_id = excluded.channel_id",
+    )
+    .bind(guild_id)
+    .bind(channel_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_guild_message(pool: &SqlitePool, guild_id: i64, message_id: i64) -> sqlx::Result<()> {
+    sqlx::query("UPDATE guild_config SET message_id = ?1 WHERE guild_id = ?2")
+        .bind(message_id)
+        .bind(guild_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[derive(FromRow, Clone)]
+pub struct AccountRow {
+    pub puuid: String,
+    pub platform: String,
+    pub riot_id: Option<String>,
+}
+
+pub async fn register_account(
+    pool: &SqlitePool,
+    guild_id: i64,
+    puuid: &str,
+    platform: &str,
+    riot_id: &str,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO accounts (puuid, platform, guild_id, riot_id) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(puuid, guild_id) DO UPDATE SET riot_id = excluded.riot_id",
+    )
+    .bind(puuid)
+    .bind(platform)
+    .bind(guild_id)
+    .bind(riot_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn accounts_for_guild(pool: &SqlitePool, guild_id: i64) -> sqlx::Result<Vec<AccountRow>> {
+    sqlx::query_as::<_, AccountRow>(
+        "SELECT puuid, platform, riot_id FROM accounts WHERE guild_id = ?1",
+    )
+    .bind(guild_id)
+    .fetch_all(pool)
+    .await
+}
+
+#[derive(FromRow, Clone)]
+pub struct SnapshotRow {
+    pub tier: String,
+    pub rank: String,
+    pub league_points: i64,
+    pub wins: i64,
+    pub losses: i64,
+}
+
+pub async fn latest_snapshot(
+    pool: &SqlitePool,
+    puuid: &str,
+    guild_id: i64,
+) -> sqlx::Result<Option<SnapshotRow>> {
+    sqlx::query_as::<_, SnapshotRow>(
+        "SELECT tier, rank, league_points, wins, losses FROM rank_snapshots
+         WHERE puuid = ?1 AND guild_id = ?2
+         ORDER BY taken_at DESC LIMIT 1",
+    )
+    .bind(puuid)
+    .bind(guild_id)
+    .fetch_optional(pool)
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_snapshot(
+    pool: &SqlitePool,
+    puuid: &str,
+    guild_id: i64,
+    tier: &str,
+    rank: &str,
+    league_points: i64,
+    wins: i64,
+    losses: i64,
+    taken_at: i64,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO rank_snapshots (puuid, guild_id, tier, rank, league_points, wins, losses, taken_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(puuid)
+    .bind(guild_id)
+    .bind(tier)
+    .bind(rank)
+    .bind(league_points)
+    .bind(wins)
+    .bind(losses)
+    .bind(taken_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
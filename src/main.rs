@@ -1,11 +1,16 @@
+mod db;
+mod metrics;
+
 use dotenv::dotenv;
-use futures::stream::StreamExt;
-use redis::{AsyncCommands, Client};
-use riven::consts::{PlatformRoute, QueueType};
+use futures::stream::{self, StreamExt};
+use riven::consts::{Division, PlatformRoute, QueueType, Tier};
 use riven::models::league_v4::LeagueEntry;
-use riven::RiotApi;
+use riven::{RiotApi, RiotApiConfig, RiotApiError};
+use sqlx::sqlite::SqlitePool;
 use std::cmp::Ordering;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{
+    collections::HashMap,
     env,
     error::Error,
     sync::{Arc, Mutex},
@@ -30,7 +35,7 @@ use twilight_model::{
     guild::Permissions,
     http::interaction::{InteractionResponse, InteractionResponseType},
     id::{
-        marker::{ApplicationMarker, ChannelMarker, MessageMarker},
+        marker::{ApplicationMarker, ChannelMarker, GuildMarker, MessageMarker},
         Id,
     },
 };
@@ -40,13 +45,38 @@ use twilight_util::builder::{
     InteractionResponseDataBuilder,
 };
 
+use metrics::{Metrics, RefreshStats};
+
+/// A guild's scoreboard configuration: which channel it lives in and which
+/// message is being edited in place every refresh.
+#[derive(Default, Clone, Copy)]
+struct GuildConfig {
+    scoreboard_channel: Option<Id<ChannelMarker>>,
+    scoreboard_message: Option<Id<MessageMarker>>,
+}
+
+/// How long a cached `league_v4` lookup is considered fresh enough to reuse.
+///
+/// Keeps an admin mashing `/refresh` (or the daily cycle overlapping one)
+/// from burning through the rate limit on data that hasn't changed.
+const LEAGUE_CACHE_TTL: Duration = Duration::from_secs(120);
+
+/// How many `league_v4` lookups we let run concurrently during a refresh.
+const MAX_IN_FLIGHT_LOOKUPS: usize = 8;
+
+struct CachedLeagueEntries {
+    fetched_at: Instant,
+    entries: Vec<LeagueEntry>,
+}
+
 struct Context {
     http: Arc<HttpClient>,
     application_id: Id<ApplicationMarker>,
     riot_api: RiotApi,
-    db: Client,
-    scoreboard_channel: Arc<Mutex<Option<Id<ChannelMarker>>>>,
-    scoreboard_message: Arc<Mutex<Option<Id<MessageMarker>>>>,
+    db: SqlitePool,
+    guilds: Arc<Mutex<HashMap<Id<GuildMarker>, GuildConfig>>>,
+    metrics: Metrics,
+    league_cache: Arc<Mutex<HashMap<(PlatformRoute, String), CachedLeagueEntries>>>,
 }
 
 #[tokio::main]
@@ -55,28 +85,26 @@ async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     let token = env::var("DISCORD_TOKEN")?;
     let api_key = env::var("RIOT_API_KEY")?;
-    let db_password = env::var("REDIS_PASSWORD")?;
-    let db_hostname = env::var("REDIS_HOSTNAME")?;
-    let db_port = env::var("REDIS_PORT")?;
-    let db_url = format!(
-        "redis://default:{}@{}:{}",
-        db_password, db_hostname, db_port
-    );
-
-    let riot_api = RiotApi::new(api_key);
-
-    let client = Client::open(db_url)?;
-    let scoreboard_channel: Option<Id<ChannelMarker>> = {
-        let mut conn = client.get_async_connection().await?;
-        conn.get::<&str, Option<u64>>("scoreboard_channel")
-            .await?
-            .map(Id::new)
-    };
-    let scoreboard_message: Option<Id<MessageMarker>> = {
-        let mut conn = client.get_async_connection().await?;
-        conn.get::<&str, Option<u64>>("scoreboard_message")
-            .await?
-            .map(Id::new)
+    let database_url = env::var("DATABASE_URL")?;
+
+    // Route lookups through riven's own rate limiter instead of relying on ad-hoc
+    // sleeps; the default config already tracks Riot's per-app/per-method buckets.
+    let riot_api = RiotApi::new(RiotApiConfig::with_key(api_key));
+
+    let pool = db::connect(&database_url).await?;
+    let guilds = {
+        let rows = db::all_guild_configs(&pool).await?;
+        rows.into_iter()
+            .map(|row| {
+                (
+                    Id::new(row.guild_id as u64),
+                    GuildConfig {
+                        scoreboard_channel: Some(Id::new(row.channel_id as u64)),
+                        scoreboard_message: row.message_id.map(|id| Id::new(id as u64)),
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>()
     };
     let config = ConfigBuilder::new(token.clone(), Intents::GUILD_MESSAGES)
         .presence(UpdatePresencePayload::new(
@@ -114,14 +142,18 @@ async fn main() -> anyhow::Result<()> {
             CommandType::ChatInput,
         )
         .dm_permission(false)
+        .option(
+            StringBuilder::new("riot_id", "Your Riot ID, e.g. Name#TAG")
+                .required(true)
+                .min_length(3)
+                .max_length(22),
+        )
         .option(
             StringBuilder::new(
-                "username",
-                "The summoner name to track. Only works for NA atm",
+                "region",
+                "The region the summoner plays on (e.g. na, euw, kr)",
             )
-            .required(true)
-            .min_length(3)
-            .max_length(16),
+            .required(true),
         )
         .build(),
         CommandBuilder::new(
@@ -132,6 +164,14 @@ async fn main() -> anyhow::Result<()> {
         .dm_permission(false)
         .default_member_permissions(Permissions::ADMINISTRATOR)
         .build(),
+        CommandBuilder::new(
+            "refresh",
+            "Immediately rebuild this server's scoreboard",
+            CommandType::ChatInput,
+        )
+        .dm_permission(false)
+        .default_member_permissions(Permissions::ADMINISTRATOR)
+        .build(),
     ];
 
     interaction.set_global_commands(&commands).await?;
@@ -140,9 +180,10 @@ async fn main() -> anyhow::Result<()> {
         http: Arc::clone(&http),
         application_id: current_app.id,
         riot_api,
-        db: client,
-        scoreboard_channel: Arc::new(Mutex::new(scoreboard_channel)),
-        scoreboard_message: Arc::new(Mutex::new(scoreboard_message)),
+        db: pool,
+        guilds: Arc::new(Mutex::new(guilds)),
+        metrics: Metrics::from_env(),
+        league_cache: Arc::new(Mutex::new(HashMap::new())),
     });
     let thread_ctx = Arc::clone(&ctx);
     tokio::spawn(async move {
@@ -188,11 +229,15 @@ async fn handle_event(event: Event, ctx: Arc<Context>) -> Result<(), Box<dyn Err
                     } else {
                         return Err("No application data".into());
                     };
+                let guild_id = match interaction.guild_id {
+                    Some(guild_id) => guild_id,
+                    None => return Err("Command used outside of a guild".into()),
+                };
                 let name = data.name.as_str();
                 tracing::info!("Slash command used: {}", name);
                 match name {
                     "register" => {
-                        let response = handle_register(data, &ctx).await;
+                        let response = handle_register(data, &ctx, guild_id).await;
                         ctx.http
                             .interaction(ctx.application_id)
                             .create_response(interaction.id, &interaction.token, &response)
@@ -212,15 +257,14 @@ async fn handle_event(event: Event, ctx: Arc<Context>) -> Result<(), Box<dyn Err
                             .create_response(interaction.id, &interaction.token, &response)
                             .await?;
                         let channel = interaction.channel_id.unwrap();
-                        let mut conn = ctx.db.get_async_connection().await?;
-                        conn.set::<&str, u64, String>("scoreboard_channel", channel.get())
-                            .await
-                            .unwrap();
+                        db::set_guild_channel(&ctx.db, guild_id.get() as i64, channel.get() as i64)
+                            .await?;
                         {
-                            let mut scoreboard = ctx.scoreboard_channel.lock().unwrap();
-                            *scoreboard = Some(channel);
+                            let mut guilds = ctx.guilds.lock().unwrap();
+                            let config = guilds.entry(guild_id).or_default();
+                            config.scoreboard_channel = Some(channel);
                         }
-                        launch_scoreboard(&ctx).await?;
+                        launch_scoreboard(&ctx, guild_id).await?;
                         ctx.http
                             .interaction(ctx.application_id)
                             .update_response(&interaction.token)
@@ -228,6 +272,36 @@ async fn handle_event(event: Event, ctx: Arc<Context>) -> Result<(), Box<dyn Err
                             .unwrap()
                             .await?;
                     }
+                    "refresh" => {
+                        let has_scoreboard = {
+                            let guilds = ctx.guilds.lock().unwrap();
+                            guilds.get(&guild_id).map_or(false, |config| {
+                                config.scoreboard_channel.is_some()
+                                    && config.scoreboard_message.is_some()
+                            })
+                        };
+                        let content = if has_scoreboard {
+                            let start = Instant::now();
+                            let stats = update_guild_scoreboard(&ctx, guild_id, false).await?;
+                            ctx.metrics.record_refresh(&stats, start.elapsed()).await;
+                            "Refreshed the scoreboard"
+                        } else {
+                            "No scoreboard set up yet, run /leaderboard first"
+                        };
+                        let response = InteractionResponse {
+                            kind: InteractionResponseType::ChannelMessageWithSource,
+                            data: Some(
+                                InteractionResponseDataBuilder::new()
+                                    .flags(MessageFlags::EPHEMERAL)
+                                    .content(content)
+                                    .build(),
+                            ),
+                        };
+                        ctx.http
+                            .interaction(ctx.application_id)
+                            .create_response(interaction.id, &interaction.token, &response)
+                            .await?;
+                    }
                     _ => {
                         tracing::warn!("Unknown command: {}", name);
                         return Ok(());
@@ -240,8 +314,13 @@ async fn handle_event(event: Event, ctx: Arc<Context>) -> Result<(), Box<dyn Err
         },
         Event::Ready(_) => tracing::info!("Bot is ready"),
         Event::MessageCreate(msg) => {
-            if msg.channel_id == ctx.scoreboard_channel.lock().unwrap().unwrap() && !msg.author.bot
-            {
+            let is_scoreboard_channel = {
+                let guilds = ctx.guilds.lock().unwrap();
+                guilds
+                    .values()
+                    .any(|config| config.scoreboard_channel == Some(msg.channel_id))
+            };
+            if is_scoreboard_channel && !msg.author.bot {
                 ctx.http.delete_message(msg.channel_id, msg.id).await?;
             }
         }
@@ -253,8 +332,17 @@ async fn handle_event(event: Event, ctx: Arc<Context>) -> Result<(), Box<dyn Err
     Ok(())
 }
 
-async fn launch_scoreboard(ctx: &Arc<Context>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let channel = ctx.scoreboard_channel.lock().unwrap().unwrap();
+async fn launch_scoreboard(
+    ctx: &Arc<Context>,
+    guild_id: Id<GuildMarker>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let channel = {
+        let guilds = ctx.guilds.lock().unwrap();
+        guilds
+            .get(&guild_id)
+            .and_then(|config| config.scoreboard_channel)
+            .unwrap()
+    };
     let message = ctx
         .http
         .create_message(channel)
@@ -263,70 +351,198 @@ async fn launch_scoreboard(ctx: &Arc<Context>) -> Result<(), Box<dyn Error + Sen
         .model()
         .await?;
     {
-        let mut scoreboard = ctx.scoreboard_message.lock().unwrap();
-        *scoreboard = Some(message.id);
+        let mut guilds = ctx.guilds.lock().unwrap();
+        let config = guilds.entry(guild_id).or_default();
+        config.scoreboard_message = Some(message.id);
     }
-    let mut conn = ctx.db.get_async_connection().await?;
-    conn.set::<&str, u64, String>("scoreboard_message", message.id.get())
-        .await
-        .unwrap();
-    update_scoreboard(ctx).await?;
+    db::set_guild_message(&ctx.db, guild_id.get() as i64, message.id.get() as i64).await?;
+    // Like `/refresh`, this isn't the daily cycle — don't lay down a snapshot
+    // row at an arbitrary time and skew tomorrow's Δ baseline.
+    update_guild_scoreboard(ctx, guild_id, false).await?;
     Ok(())
 }
 
+/// Runs the daily refresh cycle across every guild, writing a rank snapshot
+/// for each tracked account so the next cycle has a baseline to diff against.
 async fn update_scoreboard(ctx: &Arc<Context>) -> Result<(), Box<dyn Error + Send + Sync>> {
-    {
-        if ctx.scoreboard_channel.lock().unwrap().is_none()
-            || ctx.scoreboard_message.lock().unwrap().is_none()
-        {
-            return Ok(());
-        }
+    let start = Instant::now();
+    let guild_ids: Vec<Id<GuildMarker>> = {
+        let guilds = ctx.guilds.lock().unwrap();
+        guilds.keys().copied().collect()
+    };
+    let mut stats = RefreshStats::default();
+    for guild_id in guild_ids {
+        let guild_stats = update_guild_scoreboard(ctx, guild_id, true).await?;
+        stats.accounts_tracked += guild_stats.accounts_tracked;
+        stats.api_calls += guild_stats.api_calls;
+        stats.failed_lookups += guild_stats.failed_lookups;
     }
-    let mut conn = ctx.db.get_async_connection().await?;
-    let iter = conn.scan::<String>().await?;
-    let keys: Vec<String> = iter.collect().await;
+    ctx.metrics.record_refresh(&stats, start.elapsed()).await;
+    Ok(())
+}
+
+/// Rebuilds one guild's scoreboard embed.
+///
+/// `write_snapshots` controls whether this cycle lays down a new baseline row
+/// in `rank_snapshots`. The daily cycle always does; on-demand `/refresh`
+/// doesn't, so mashing the button can't collapse the "daily LP movement"
+/// Δ down to "movement since the last click" (or zero it out entirely while
+/// `LEAGUE_CACHE_TTL` is still serving the same cached entries).
+async fn update_guild_scoreboard(
+    ctx: &Arc<Context>,
+    guild_id: Id<GuildMarker>,
+    write_snapshots: bool,
+) -> Result<RefreshStats, Box<dyn Error + Send + Sync>> {
+    let (scoreboard_channel, scoreboard_message) = {
+        let guilds = ctx.guilds.lock().unwrap();
+        match guilds.get(&guild_id) {
+            Some(config) => (config.scoreboard_channel, config.scoreboard_message),
+            None => return Ok(RefreshStats::default()),
+        }
+    };
+    let (scoreboard_channel, scoreboard_message) = match (scoreboard_channel, scoreboard_message) {
+        (Some(channel), Some(message)) => (channel, message),
+        _ => return Ok(RefreshStats::default()),
+    };
+    let summoners = db::accounts_for_guild(&ctx.db, guild_id.get() as i64).await?;
+    let riot_ids: HashMap<String, String> = summoners
+        .iter()
+        .filter_map(|summoner| {
+            summoner
+                .riot_id
+                .clone()
+                .map(|riot_id| (summoner.puuid.clone(), riot_id))
+        })
+        .collect();
+    let mut stats = RefreshStats::default();
+    let valid_summoners: Vec<(db::AccountRow, PlatformRoute)> = summoners
+        .into_iter()
+        .filter_map(|summoner| match parse_platform_route(&summoner.platform) {
+            Some(platform) => Some((summoner, platform)),
+            None => {
+                tracing::warn!("No platform stored for {}, skipping", summoner.puuid);
+                None
+            }
+        })
+        .collect();
+    stats.accounts_tracked = valid_summoners.len() as u64;
+    let lookups = stream::iter(valid_summoners).map(|(summoner, platform)| {
+        let ctx = Arc::clone(ctx);
+        async move {
+            let entries = fetch_league_entries_cached(&ctx, platform, &summoner.puuid).await;
+            (summoner, entries)
+        }
+    });
+    let results: Vec<(db::AccountRow, Result<(Vec<LeagueEntry>, u64), RiotApiError>)> =
+        lookups.buffer_unordered(MAX_IN_FLIGHT_LOOKUPS).collect().await;
     let mut accounts = vec![];
-    //TODO probably need to make this caching of some sort to not get rate limited
-    for key in keys {
-        if !(key == "scoreboard_channel" || key == "scoreboard_message") {
-            let summoner = ctx
-                .riot_api
-                .league_v4()
-                .get_league_entries_for_summoner(PlatformRoute::NA1, &key)
-                .await?;
-            summoner.iter().for_each(|entry| {
-                if entry.queue_type == QueueType::RANKED_SOLO_5x5 {
-                    if entry.veteran {
-                        tracing::info!("{} is a veteran", entry.summoner_name);
-                    }
-                    if entry.tier.unwrap().is_ranked() {
-                        accounts.push(entry.clone());
-                    }
+    for (summoner, result) in results {
+        let (entries, live_calls) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::warn!(?err, "failed to fetch league entries for {}", summoner.puuid);
+                stats.failed_lookups += 1;
+                continue;
+            }
+        };
+        stats.api_calls += live_calls;
+        entries.iter().for_each(|entry| {
+            if entry.queue_type == QueueType::RANKED_SOLO_5x5 {
+                if entry.veteran {
+                    tracing::info!("{} is a veteran", entry.summoner_name);
                 }
-            });
-        }
+                if entry.tier.unwrap().is_ranked() {
+                    accounts.push(entry.clone());
+                }
+            }
+        });
     }
     accounts.sort_by(compare);
+    let guild_id_raw = guild_id.get() as i64;
+    let taken_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    // Read last cycle's snapshot for every tracked account before writing this
+    // cycle's, so the Δ below always compares against the prior cycle rather
+    // than a row this same pass just inserted.
+    let mut previous_by_puuid = HashMap::with_capacity(accounts.len());
+    for account in &accounts {
+        let previous = db::latest_snapshot(&ctx.db, &account.puuid, guild_id_raw).await?;
+        previous_by_puuid.insert(account.puuid.clone(), previous);
+    }
+
+    // Persist a snapshot for every tracked account, not just the ones shown on
+    // the scoreboard, so an account's grind below the top 10 isn't invisible
+    // the moment it climbs into view. Only the daily cycle does this — an
+    // on-demand `/refresh` must not move the Δ baseline.
+    if write_snapshots {
+        for account in &accounts {
+            let tier = account.tier.unwrap();
+            let rank = account.rank.unwrap();
+            db::insert_snapshot(
+                &ctx.db,
+                &account.puuid,
+                guild_id_raw,
+                tier_code(tier),
+                division_code(rank),
+                account.league_points as i64,
+                account.wins as i64,
+                account.losses as i64,
+                taken_at,
+            )
+            .await?;
+        }
+    }
+
     {
-        let scoreboard_channel = *ctx.scoreboard_channel.lock().unwrap();
-        let scoreboard_message = *ctx.scoreboard_message.lock().unwrap();
         let mut rank_content = String::new();
         rank_content.push_str(&format!(
-            "`{:<2}` `{:^16}` `{:^14}` `{:^6}` `{:^4}` `{:^4}` `{:^3}`\n",
-            "#", "Summoner", "Rank", "LP", "Win", "Loss", "WL%"
+            "`{:<2}` `{:^16}` `{:^14}` `{:^6}` `{:^4}` `{:^4}` `{:^3}` `{:^10}`\n",
+            "#", "Summoner", "Rank", "LP", "Win", "Loss", "WL%", "Δ"
         ));
         for (idx, account) in accounts.iter().enumerate().take(10) {
+            let tier = account.tier.unwrap();
+            let rank = account.rank.unwrap();
+            let previous = previous_by_puuid.get(&account.puuid).cloned().flatten();
+            let delta = previous.as_ref().map(|previous| {
+                let previous_score = rank_score(
+                    parse_tier(&previous.tier).unwrap_or(tier),
+                    parse_division(&previous.rank).unwrap_or(rank),
+                    previous.league_points as i32,
+                );
+                let current_score = rank_score(tier, rank, account.league_points);
+                let lp_diff = current_score - previous_score;
+                let games_diff = (account.wins + account.losses) as i64
+                    - (previous.wins + previous.losses);
+                (lp_diff, games_diff)
+            });
+            let display_name = riot_ids
+                .get(&account.puuid)
+                .map(String::as_str)
+                .unwrap_or(&account.summoner_name);
             rank_content.push_str(&format!(
                 "`{:<2}` `{:<16}` `{:<10} {:>3}` `{:>4}LP` `{:>3}W` `{:>3}L` `{}%`",
                 idx + 1,
-                account.summoner_name,
-                account.tier.unwrap(),
-                account.rank.unwrap(),
+                display_name,
+                tier,
+                rank,
                 account.league_points,
                 account.wins,
                 account.losses,
                 ((account.wins as f64 / (account.wins + account.losses) as f64) * 100_f64) as i64
             ));
+            match delta {
+                Some((lp_diff, games_diff)) if lp_diff > 0 => {
+                    rank_content.push_str(&format!(" `🔼 +{}LP {}G`", lp_diff, games_diff))
+                }
+                Some((lp_diff, games_diff)) if lp_diff < 0 => {
+                    rank_content.push_str(&format!(" `🔽 {}LP {}G`", lp_diff, games_diff))
+                }
+                Some((_, games_diff)) => rank_content.push_str(&format!(" `-- 0LP {}G`", games_diff)),
+                None => rank_content.push_str(" `new`"),
+            }
             if account.veteran {
                 rank_content.push_str(" 👴\n");
             } else if account.hot_streak {
@@ -335,21 +551,202 @@ async fn update_scoreboard(ctx: &Arc<Context>) -> Result<(), Box<dyn Error + Sen
                 rank_content.push('\n');
             }
         }
-        dbg!(rank_content.len());
         let embed = EmbedBuilder::new()
             .title("OME SoloQ Leaderboard")
             .description(rank_content)
             .validate()?
             .build();
         ctx.http
-            .update_message(scoreboard_channel.unwrap(), scoreboard_message.unwrap())
+            .update_message(scoreboard_channel, scoreboard_message)
             .content(None)
             .unwrap()
             .embeds(Some(&[embed]))
             .unwrap()
             .await?;
     }
-    Ok(())
+    Ok(stats)
+}
+
+/// Fetches a summoner's ranked entries, reusing a cached result from within
+/// `LEAGUE_CACHE_TTL` instead of hitting `league_v4` again. Returns the number
+/// of live Riot API calls this lookup made (0 on a cache hit) so callers can
+/// track real API usage.
+///
+/// We only persist the account's PUUID (its stable identifier), so every live
+/// fetch re-resolves the current encrypted summoner ID first — `league_v4`
+/// still takes that, not the PUUID — which means a cache miss costs two calls,
+/// not one (just one when the PUUID has no summoner on that platform).
+async fn fetch_league_entries_cached(
+    ctx: &Context,
+    platform: PlatformRoute,
+    puuid: &str,
+) -> Result<(Vec<LeagueEntry>, u64), RiotApiError> {
+    let cache_key = (platform, puuid.to_string());
+    {
+        let cache = ctx.league_cache.lock().unwrap();
+        if let Some(cached) = cache.get(&cache_key) {
+            if cached.fetched_at.elapsed() < LEAGUE_CACHE_TTL {
+                return Ok((cached.entries.clone(), 0));
+            }
+        }
+    }
+    let summoner = ctx.riot_api.summoner_v4().get_by_puuid(platform, puuid).await?;
+    let (entries, live_calls) = match summoner {
+        Some(summoner) => {
+            let entries = ctx
+                .riot_api
+                .league_v4()
+                .get_league_entries_for_summoner(platform, &summoner.id)
+                .await?;
+            (entries, 2)
+        }
+        None => (Vec::new(), 1),
+    };
+    {
+        let mut cache = ctx.league_cache.lock().unwrap();
+        cache.insert(
+            cache_key,
+            CachedLeagueEntries {
+                fetched_at: Instant::now(),
+                entries: entries.clone(),
+            },
+        );
+    }
+    Ok((entries, live_calls))
+}
+
+/// Maps a user-supplied region string to the `PlatformRoute` it refers to.
+///
+/// Accepts both the common shorthand players use (e.g. "na", "euw") and the
+/// platform's own route string (e.g. "na1", "euw1"), since we round-trip the
+/// latter through the database.
+fn parse_platform_route(input: &str) -> Option<PlatformRoute> {
+    match input.to_lowercase().as_str() {
+        "na" | "na1" => Some(PlatformRoute::NA1),
+        "euw" | "euw1" => Some(PlatformRoute::EUW1),
+        "eune" | "eun1" => Some(PlatformRoute::EUN1),
+        "kr" => Some(PlatformRoute::KR),
+        "jp" | "jp1" => Some(PlatformRoute::JP1),
+        "br" | "br1" => Some(PlatformRoute::BR1),
+        "lan" | "la1" => Some(PlatformRoute::LA1),
+        "las" | "la2" => Some(PlatformRoute::LA2),
+        "oce" | "oc1" => Some(PlatformRoute::OC1),
+        "tr" | "tr1" => Some(PlatformRoute::TR1),
+        "ru" => Some(PlatformRoute::RU),
+        "ph" | "ph2" => Some(PlatformRoute::PH2),
+        "sg" | "sg2" => Some(PlatformRoute::SG2),
+        "th" | "th2" => Some(PlatformRoute::TH2),
+        "tw" | "tw2" => Some(PlatformRoute::TW2),
+        "vn" | "vn2" => Some(PlatformRoute::VN2),
+        _ => None,
+    }
+}
+
+/// Canonical string we persist a `Tier` as, independent of its `Display` impl.
+fn tier_code(tier: Tier) -> &'static str {
+    match tier {
+        Tier::IRON => "IRON",
+        Tier::BRONZE => "BRONZE",
+        Tier::SILVER => "SILVER",
+        Tier::GOLD => "GOLD",
+        Tier::PLATINUM => "PLATINUM",
+        Tier::EMERALD => "EMERALD",
+        Tier::DIAMOND => "DIAMOND",
+        Tier::MASTER => "MASTER",
+        Tier::GRANDMASTER => "GRANDMASTER",
+        Tier::CHALLENGER => "CHALLENGER",
+        _ => "UNRANKED",
+    }
+}
+
+fn parse_tier(input: &str) -> Option<Tier> {
+    match input {
+        "IRON" => Some(Tier::IRON),
+        "BRONZE" => Some(Tier::BRONZE),
+        "SILVER" => Some(Tier::SILVER),
+        "GOLD" => Some(Tier::GOLD),
+        "PLATINUM" => Some(Tier::PLATINUM),
+        "EMERALD" => Some(Tier::EMERALD),
+        "DIAMOND" => Some(Tier::DIAMOND),
+        "MASTER" => Some(Tier::MASTER),
+        "GRANDMASTER" => Some(Tier::GRANDMASTER),
+        "CHALLENGER" => Some(Tier::CHALLENGER),
+        _ => None,
+    }
+}
+
+/// Canonical string we persist a `Division` as, independent of its `Display` impl.
+fn division_code(division: Division) -> &'static str {
+    match division {
+        Division::I => "I",
+        Division::II => "II",
+        Division::III => "III",
+        Division::IV => "IV",
+    }
+}
+
+fn parse_division(input: &str) -> Option<Division> {
+    match input {
+        "I" => Some(Division::I),
+        "II" => Some(Division::II),
+        "III" => Some(Division::III),
+        "IV" => Some(Division::IV),
+        _ => None,
+    }
+}
+
+fn tier_index(tier: Tier) -> i64 {
+    match tier {
+        Tier::IRON => 0,
+        Tier::BRONZE => 1,
+        Tier::SILVER => 2,
+        Tier::GOLD => 3,
+        Tier::PLATINUM => 4,
+        Tier::EMERALD => 5,
+        Tier::DIAMOND => 6,
+        Tier::MASTER => 7,
+        Tier::GRANDMASTER => 8,
+        Tier::CHALLENGER => 9,
+        _ => 0,
+    }
+}
+
+fn division_index(division: Division) -> i64 {
+    match division {
+        Division::IV => 0,
+        Division::III => 1,
+        Division::II => 2,
+        Division::I => 3,
+    }
+}
+
+/// Whether `tier` is one of the apex tiers, which Riot doesn't split into
+/// divisions and which have effectively unbounded LP.
+fn is_apex_tier(tier: Tier) -> bool {
+    matches!(tier, Tier::MASTER | Tier::GRANDMASTER | Tier::CHALLENGER)
+}
+
+/// The score of stepping one rung past Diamond I — the baseline every apex
+/// tier's LP is added on top of.
+fn apex_baseline() -> i64 {
+    tier_index(Tier::DIAMOND) * 400 + (division_index(Division::I) + 1) * 100
+}
+
+/// Flattens tier/division/LP into a single linear scale so a promotion or
+/// demotion across division (or tier) boundaries nets out to a sane LP delta
+/// instead of looking like a 100+ point swing.
+///
+/// Apex tiers (Master/Grandmaster/Challenger) have no divisions, so `division`
+/// is meaningless for them; they're collapsed onto one shared baseline just
+/// past Diamond I, so crossing into them nets a single 100-point rung plus the
+/// real LP change (like any division promotion), and moving between them nets
+/// only the LP change, instead of a full 400-point tier jump.
+fn rank_score(tier: Tier, division: Division, lp: i32) -> i64 {
+    if is_apex_tier(tier) {
+        apex_baseline() + lp as i64
+    } else {
+        tier_index(tier) * 400 + division_index(division) * 100 + lp as i64
+    }
 }
 
 fn compare(a: &LeagueEntry, b: &LeagueEntry) -> Ordering {
@@ -372,51 +769,175 @@ fn compare(a: &LeagueEntry, b: &LeagueEntry) -> Ordering {
     }
 }
 
-async fn handle_register(data: &CommandData, ctx: &Arc<Context>) -> InteractionResponse {
-    let username = if let Some(opt) = data.options.get(0) {
-        if let CommandOptionValue::String(username) = &opt.value {
-            username.as_str()
+async fn handle_register(
+    data: &CommandData,
+    ctx: &Arc<Context>,
+    guild_id: Id<GuildMarker>,
+) -> InteractionResponse {
+    let riot_id = if let Some(opt) = data.options.iter().find(|opt| opt.name == "riot_id") {
+        if let CommandOptionValue::String(riot_id) = &opt.value {
+            riot_id.as_str()
         } else {
+            ctx.metrics.record_register(false).await;
             return InteractionResponse {
                 kind: InteractionResponseType::ChannelMessageWithSource,
                 data: Some(
                     InteractionResponseDataBuilder::new()
-                        .content("Invalid username")
+                        .content("Invalid Riot ID")
                         .build(),
                 ),
             };
         }
     } else {
+        ctx.metrics.record_register(false).await;
         return InteractionResponse {
             kind: InteractionResponseType::ChannelMessageWithSource,
             data: Some(
                 InteractionResponseDataBuilder::new()
-                    .content("Invalid username")
+                    .content("Invalid Riot ID")
                     .build(),
             ),
         };
     };
-    let summoner = ctx
+    let (game_name, tag_line) = match riot_id.split_once('#') {
+        Some((game_name, tag_line)) if !game_name.is_empty() && !tag_line.is_empty() => {
+            (game_name, tag_line)
+        }
+        _ => {
+            ctx.metrics.record_register(false).await;
+            return InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .content("Riot ID must be in the form Name#TAG")
+                        .build(),
+                ),
+            };
+        }
+    };
+    let platform = if let Some(opt) = data.options.iter().find(|opt| opt.name == "region") {
+        if let CommandOptionValue::String(region) = &opt.value {
+            match parse_platform_route(region) {
+                Some(platform) => platform,
+                None => {
+                    ctx.metrics.record_register(false).await;
+                    return InteractionResponse {
+                        kind: InteractionResponseType::ChannelMessageWithSource,
+                        data: Some(
+                            InteractionResponseDataBuilder::new()
+                                .content("Invalid region")
+                                .build(),
+                        ),
+                    };
+                }
+            }
+        } else {
+            ctx.metrics.record_register(false).await;
+            return InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .content("Invalid region")
+                        .build(),
+                ),
+            };
+        }
+    } else {
+        ctx.metrics.record_register(false).await;
+        return InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .content("Invalid region")
+                    .build(),
+            ),
+        };
+    };
+    let account = match ctx
         .riot_api
-        .summoner_v4()
-        .get_by_summoner_name(PlatformRoute::NA1, username)
+        .account_v1()
+        .get_by_riot_id(platform.to_regional(), game_name, tag_line)
         .await
-        .expect("Failed to lookup summoner");
-    let summoner = match summoner {
-        Some(summoner) => summoner,
+    {
+        Ok(account) => account,
+        Err(err) => {
+            tracing::warn!(?err, "failed to look up Riot account for {}", riot_id);
+            ctx.metrics.record_register(false).await;
+            return InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .content("Couldn't reach Riot's servers, try again in a bit")
+                        .build(),
+                ),
+            };
+        }
+    };
+    let account = match account {
+        Some(account) => account,
         None => {
+            ctx.metrics.record_register(false).await;
+            return InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .content("Invalid Riot ID")
+                        .build(),
+                ),
+            };
+        }
+    };
+    // We don't persist the encrypted summoner ID itself (it rotates); this lookup
+    // only confirms the account actually has a summoner on the chosen platform.
+    match ctx
+        .riot_api
+        .summoner_v4()
+        .get_by_puuid(platform, &account.puuid)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            ctx.metrics.record_register(false).await;
+            return InteractionResponse {
+                kind: InteractionResponseType::ChannelMessageWithSource,
+                data: Some(
+                    InteractionResponseDataBuilder::new()
+                        .content("That Riot ID hasn't played on this region")
+                        .build(),
+                ),
+            };
+        }
+        Err(err) => {
+            tracing::warn!(?err, "failed to look up summoner for {}", riot_id);
+            ctx.metrics.record_register(false).await;
             return InteractionResponse {
                 kind: InteractionResponseType::ChannelMessageWithSource,
                 data: Some(
                     InteractionResponseDataBuilder::new()
-                        .content("Invalid summoner name")
+                        .content("Couldn't reach Riot's servers, try again in a bit")
                         .build(),
                 ),
             };
         }
     };
-    let mut con = ctx.db.get_async_connection().await.unwrap();
-    con.set_nx::<String, i32, i8>(summoner.id, 1).await.unwrap();
+    db::register_account(
+        &ctx.db,
+        guild_id.get() as i64,
+        &account.puuid,
+        &platform.to_string(),
+        &format!(
+            "{}#{}",
+            account.game_name.as_deref().unwrap_or(game_name),
+            account.tag_line.as_deref().unwrap_or(tag_line)
+        ),
+    )
+    .await
+    .unwrap();
+    // Don't make the interaction response wait on an Influx round-trip —
+    // Discord only gives us a 3s window and we've already made two serial
+    // Riot calls to get here.
+    let metrics = ctx.metrics.clone();
+    tokio::spawn(async move { metrics.record_register(true).await });
     InteractionResponse {
         kind: InteractionResponseType::ChannelMessageWithSource,
         data: Some(
@@ -427,3 +948,45 @@ async fn handle_register(data: &CommandData, ctx: &Arc<Context>) -> InteractionR
         ),
     }
 }
+
+#[cfg(test)]
+mod rank_score_tests {
+    use super::*;
+
+    #[test]
+    fn diamond_one_to_master_nets_roughly_zero() {
+        let diamond = rank_score(Tier::DIAMOND, Division::I, 100);
+        let master = rank_score(Tier::MASTER, Division::I, 0);
+        assert_eq!(master - diamond, 0);
+    }
+
+    #[test]
+    fn apex_tiers_at_equal_lp_net_zero() {
+        let master = rank_score(Tier::MASTER, Division::I, 50);
+        let grandmaster = rank_score(Tier::GRANDMASTER, Division::I, 50);
+        let challenger = rank_score(Tier::CHALLENGER, Division::I, 50);
+        assert_eq!(master, grandmaster);
+        assert_eq!(grandmaster, challenger);
+    }
+
+    #[test]
+    fn apex_lp_change_passes_through_directly() {
+        let before = rank_score(Tier::MASTER, Division::I, 100);
+        let after = rank_score(Tier::GRANDMASTER, Division::I, 140);
+        assert_eq!(after - before, 40);
+    }
+
+    #[test]
+    fn cross_division_promotion_nets_small_positive() {
+        let before = rank_score(Tier::GOLD, Division::IV, 99);
+        let after = rank_score(Tier::GOLD, Division::III, 0);
+        assert_eq!(after - before, 1);
+    }
+
+    #[test]
+    fn cross_tier_promotion_nets_small_positive() {
+        let before = rank_score(Tier::SILVER, Division::I, 99);
+        let after = rank_score(Tier::GOLD, Division::IV, 0);
+        assert_eq!(after - before, 1);
+    }
+}
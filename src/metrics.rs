@@ -0,0 +1,92 @@
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Optional InfluxDB line-protocol sink for operator-facing health metrics.
+///
+/// Disabled (every call becomes a no-op) unless `INFLUX_URL`, `INFLUX_TOKEN`,
+/// and `INFLUX_BUCKET` are all set, so the bot works the same without an
+/// Influx instance around.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    http: reqwest::Client,
+    url: String,
+    token: String,
+    bucket: String,
+}
+
+/// Counters accumulated over one refresh, of a single guild's scoreboard or of
+/// every guild's.
+#[derive(Default)]
+pub struct RefreshStats {
+    pub accounts_tracked: u64,
+    pub api_calls: u64,
+    pub failed_lookups: u64,
+}
+
+impl Metrics {
+    pub fn from_env() -> Self {
+        let configured = (
+            env::var("INFLUX_URL"),
+            env::var("INFLUX_TOKEN"),
+            env::var("INFLUX_BUCKET"),
+        );
+        let inner = match configured {
+            (Ok(url), Ok(token), Ok(bucket)) => Some(Arc::new(Inner {
+                http: reqwest::Client::new(),
+                url,
+                token,
+                bucket,
+            })),
+            _ => {
+                tracing::info!(
+                    "Influx metrics disabled (INFLUX_URL/INFLUX_TOKEN/INFLUX_BUCKET not set)"
+                );
+                None
+            }
+        };
+        Self { inner }
+    }
+
+    pub async fn record_refresh(&self, stats: &RefreshStats, cycle_time: Duration) {
+        self.write(&format!(
+            "scoreboard_refresh accounts_tracked={}i,api_calls={}i,failed_lookups={}i,cycle_ms={}i",
+            stats.accounts_tracked,
+            stats.api_calls,
+            stats.failed_lookups,
+            cycle_time.as_millis()
+        ))
+        .await;
+    }
+
+    pub async fn record_register(&self, success: bool) {
+        self.write(&format!("register success={}", success)).await;
+    }
+
+    async fn write(&self, line: &str) {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let body = format!("{} {}", line, timestamp);
+        let result = inner
+            .http
+            .post(format!("{}/api/v2/write", inner.url))
+            .query(&[("bucket", inner.bucket.as_str()), ("precision", "ns")])
+            .header("Authorization", format!("Token {}", inner.token))
+            .body(body)
+            .send()
+            .await;
+        if let Err(err) = result {
+            tracing::warn!(?err, "failed to write metrics to influx");
+        }
+    }
+}